@@ -0,0 +1,143 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! A small, self-contained ChaCha20-based PRNG.
+//!
+//! Unlike `XorShiftRng`, this is suitable for seeding `sodiumoxide`'s key-generation functions:
+//! the output stream is the ChaCha20 block function run over an incrementing counter, seeded once
+//! from a 32-byte key.  It implements [`rand::Rng`](../../rand/trait.Rng.html) like any other
+//! generator in this crate, refilling its 64-byte output block lazily as it is drained.
+
+use rand::Rng;
+
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+const ROUNDS: usize = 20;
+
+/// A ChaCha20 stream-cipher PRNG, seeded from a 32-byte key.
+pub struct ChaChaRng {
+    key: [u32; 8],
+    counter: u64,
+    block: [u32; 16],
+    index: usize,
+}
+
+impl ChaChaRng {
+    /// Creates a new generator seeded from `seed`.
+    pub fn from_seed(seed: [u8; 32]) -> ChaChaRng {
+        let mut key = [0u32; 8];
+        for (word, chunk) in key.iter_mut().zip(seed.chunks(4)) {
+            *word = u32::from(chunk[0]) | (u32::from(chunk[1]) << 8) |
+                    (u32::from(chunk[2]) << 16) | (u32::from(chunk[3]) << 24);
+        }
+        let mut rng = ChaChaRng {
+            key: key,
+            counter: 0,
+            block: [0u32; 16],
+            // Placeholder: the `refill()` call below generates the first block and resets this.
+            index: 16,
+        };
+        rng.refill();
+        rng
+    }
+
+    /// Runs the ChaCha20 block function against the current counter and stores the resulting
+    /// 64 bytes (as 16 little-endian `u32`s) ready for `next_u32()` to hand out.
+    fn refill(&mut self) {
+        let mut state = [0u32; 16];
+        state[0..4].copy_from_slice(&CONSTANTS);
+        state[4..12].copy_from_slice(&self.key);
+        state[12] = self.counter as u32;
+        state[13] = (self.counter >> 32) as u32;
+        state[14] = 0;
+        state[15] = 0;
+
+        let mut working = state;
+        for _ in 0..(ROUNDS / 2) {
+            quarter_round(&mut working, 0, 4, 8, 12);
+            quarter_round(&mut working, 1, 5, 9, 13);
+            quarter_round(&mut working, 2, 6, 10, 14);
+            quarter_round(&mut working, 3, 7, 11, 15);
+            quarter_round(&mut working, 0, 5, 10, 15);
+            quarter_round(&mut working, 1, 6, 11, 12);
+            quarter_round(&mut working, 2, 7, 8, 13);
+            quarter_round(&mut working, 3, 4, 9, 14);
+        }
+
+        for i in 0..16 {
+            self.block[i] = working[i].wrapping_add(state[i]);
+        }
+        self.counter = self.counter.wrapping_add(1);
+        self.index = 0;
+    }
+}
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] ^= state[a];
+    state[d] = state[d].rotate_left(16);
+
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] ^= state[c];
+    state[b] = state[b].rotate_left(12);
+
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] ^= state[a];
+    state[d] = state[d].rotate_left(8);
+
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] ^= state[c];
+    state[b] = state[b].rotate_left(7);
+}
+
+impl Rng for ChaChaRng {
+    fn next_u32(&mut self) -> u32 {
+        if self.index >= self.block.len() {
+            self.refill();
+        }
+        let word = self.block[self.index];
+        self.index += 1;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(seed: [u8; 32], count: usize) -> Vec<u32> {
+        let mut rng = ChaChaRng::from_seed(seed);
+        (0..count).map(|_| rng.next_u32()).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_stream() {
+        assert_eq!(words([7; 32], 40), words([7; 32], 40));
+    }
+
+    #[test]
+    fn different_seeds_give_different_streams() {
+        assert!(words([7; 32], 40) != words([8; 32], 40));
+    }
+
+    #[test]
+    fn refills_past_a_single_block() {
+        // One block is 16 words; this draws past the boundary to exercise `refill()`.
+        let stream = words([9; 32], 33);
+        assert_eq!(33, stream.len());
+        assert!(stream[15] != stream[16]);
+    }
+}
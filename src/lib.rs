@@ -18,6 +18,11 @@
 //! Implementation of [libsodium's `randombytes`]
 //! (https://download.libsodium.org/doc/advanced/custom_rng.html) which allows a seeded pseudorandom
 //! number generator (PRNG) to be used.
+//!
+//! Two backends are provided: the original `XorShiftRng` (fast, but not cryptographically secure
+//! - only suitable for deterministic test vectors) and a built-in ChaCha20-based generator, which
+//! is the recommended choice for anything that feeds real key material, via
+//! [`init_with_chacha`](fn.init_with_chacha.html).
 
 #![doc(html_logo_url =
            "https://raw.githubusercontent.com/maidsafe/QA/master/Images/maidsafe_logo.png",
@@ -43,43 +48,226 @@
 #![cfg_attr(feature="clippy", deny(clippy, clippy_pedantic))]
 #![cfg_attr(feature="clippy", allow(single_match))]
 
+// `ctor` (behind `auto-init`) and `serde`/`serde_derive` (behind `serde`) are optional
+// dependencies: this crate's own Cargo.toml has to declare each of them as optional and tie it
+// to the corresponding feature for these `extern crate` declarations to resolve.
+#[cfg(feature = "auto-init")]
+extern crate ctor;
 #[macro_use]
 extern crate lazy_static;
 extern crate libc;
 extern crate rand;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
 #[cfg(test)]
 extern crate sodiumoxide;
 #[macro_use]
 extern crate unwrap;
 
+mod chacha;
+mod os_rng;
+
 use std::cell::RefCell;
 use std::ffi::CString;
+use std::io;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex;
 
 use rand::{Rng, SeedableRng, XorShiftRng};
 
+use chacha::ChaChaRng;
+
 lazy_static! {
     static ref INIT_RESULT: Mutex<Option<i32>> = Mutex::new(None);
     static ref RANDOM_BYTES_IMPL: Mutex<RandomBytesImpl> = Mutex::new(RandomBytesImpl::default());
 }
 
-thread_local!(static RNG: Rc<RefCell<XorShiftRng>> =
-    Rc::new(RefCell::new(XorShiftRng::from_seed(unwrap!(RANDOM_BYTES_IMPL.lock()).seed))));
+static THREAD_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Counts calls to `ffi::stir`, so each one derives a fresh sub-seed instead of rewinding the
+/// calling thread's stream back to its starting point.
+static STIR_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Set once `ffi::sodium_init()` has returned `0` (success) for this process.  libsodium itself
+/// has no way to "un-initialise", so if `ffi::close()` later resets `INIT_RESULT` back to `None`,
+/// a subsequent re-init must not call `sodium_init()` again: libsodium would correctly report `1`
+/// ("already initialised"), which is not an error, but `init_with_seed` would otherwise store it
+/// as the new result and brick every future call behind `Err(1)`.
+static SODIUM_EVER_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+thread_local!(static THREAD_INDEX: u64 = THREAD_COUNTER.fetch_add(1, Ordering::Relaxed));
+
+thread_local!(static RNG: Rc<RefCell<TrackedRng>> =
+    Rc::new(RefCell::new(tracked_rng_for_this_thread())));
+
+/// Builds the PRNG the calling thread should use, honouring the currently-installed
+/// `SeedingMode`.
+fn tracked_rng_for_this_thread() -> TrackedRng {
+    let random_bytes = unwrap!(RANDOM_BYTES_IMPL.lock());
+    match random_bytes.seeding_mode {
+        SeedingMode::Shared => TrackedRng::from_seed(random_bytes.seed),
+        SeedingMode::PerThread => {
+            let index = THREAD_INDEX.with(|index| *index);
+            TrackedRng::from_seed(derive_sub_seed(&random_bytes.seed, index))
+        }
+    }
+}
+
+/// Controls how a newly-spawned thread's PRNG is seeded the first time it is used.
+pub enum SeedingMode {
+    /// Every thread's generator is seeded identically to the master seed (the original
+    /// behaviour) -- two threads calling `box_::gen_keypair()` will generate identical keys.
+    Shared,
+    /// Each thread's generator is seeded from `mix(master_seed, thread_index)`, so distinct
+    /// threads get distinct keys while the sequence of sub-seeds remains reproducible across
+    /// runs.
+    PerThread,
+}
+
+/// Combines the master seed with `thread_index` via a fast keyed mixer (the SplitMix64
+/// finalizer) to derive a sub-seed that is distinct per thread but reproducible across runs.
+fn derive_sub_seed(master: &RngSeed, thread_index: u64) -> RngSeed {
+    match *master {
+        RngSeed::XorShift(words) => {
+            let mut out = [0u32; 4];
+            for (i, word) in words.iter().enumerate() {
+                out[i] = mix(u64::from(*word) ^ thread_index.wrapping_add(i as u64)) as u32;
+            }
+            RngSeed::XorShift(out)
+        }
+        RngSeed::ChaCha(bytes) => {
+            let mut out = [0u8; 32];
+            for (i, chunk) in out.chunks_mut(8).enumerate() {
+                let mut word = 0u64;
+                for byte in bytes[i * 8..i * 8 + 8].iter().rev() {
+                    word = (word << 8) | u64::from(*byte);
+                }
+                let mixed = mix(word ^ thread_index.wrapping_add(i as u64));
+                for (j, slot) in chunk.iter_mut().enumerate() {
+                    *slot = (mixed >> (8 * j)) as u8;
+                }
+            }
+            RngSeed::ChaCha(out)
+        }
+    }
+}
+
+/// Combines the master seed with `stir_count` (via [`derive_sub_seed`](fn.derive_sub_seed.html))
+/// to derive the seed `ffi::stir` reseeds the calling thread's PRNG with.  XOR-ing in a fixed tag
+/// keeps the stream `stir()` produces out of the namespace `derive_sub_seed` uses for per-thread
+/// sub-seeds, so a stir boundary never accidentally reproduces a thread's un-stirred stream.
+fn derive_stir_seed(master: &RngSeed, stir_count: u64) -> RngSeed {
+    derive_sub_seed(master, stir_count ^ 0x5354_4952_5354_4952)
+}
+
+/// A fast 64-bit mixer (the SplitMix64 finalizer), used to turn a seed/thread-index pair into a
+/// well-distributed sub-seed.
+fn mix(mut value: u64) -> u64 {
+    value ^= value >> 30;
+    value = value.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value ^= value >> 27;
+    value = value.wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^= value >> 31;
+    value
+}
+
+/// The master seed stored in `RANDOM_BYTES_IMPL`, tagged with which backend it belongs to.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum RngSeed {
+    XorShift([u32; 4]),
+    ChaCha([u8; 32]),
+}
+
+/// The actual per-thread PRNG.  A plain `enum` rather than a trait object, since `rand::Rng`'s
+/// generic `gen()` method makes it impossible to use as `Box<Rng>`.
+enum PrngBackend {
+    XorShift(XorShiftRng),
+    ChaCha(ChaChaRng),
+}
+
+impl PrngBackend {
+    fn from_seed(seed: RngSeed) -> PrngBackend {
+        match seed {
+            RngSeed::XorShift(words) => PrngBackend::XorShift(XorShiftRng::from_seed(words)),
+            RngSeed::ChaCha(bytes) => PrngBackend::ChaCha(ChaChaRng::from_seed(bytes)),
+        }
+    }
+}
+
+impl Rng for PrngBackend {
+    fn next_u32(&mut self) -> u32 {
+        match *self {
+            PrngBackend::XorShift(ref mut rng) => rng.next_u32(),
+            PrngBackend::ChaCha(ref mut rng) => rng.next_u32(),
+        }
+    }
+}
+
+/// A PRNG together with the seed it was built from and a count of how many `u32` words it has
+/// produced, which together are sufficient to reconstruct its exact state later.  See
+/// [`export_state`](fn.export_state.html).
+struct TrackedRng {
+    seed: RngSeed,
+    backend: PrngBackend,
+    words_drawn: u64,
+}
+
+impl TrackedRng {
+    fn from_seed(seed: RngSeed) -> TrackedRng {
+        TrackedRng {
+            seed: seed,
+            backend: PrngBackend::from_seed(seed),
+            words_drawn: 0,
+        }
+    }
+
+    fn snapshot(&self) -> RngState {
+        RngState {
+            seed: self.seed,
+            words_drawn: self.words_drawn,
+        }
+    }
+
+    /// Rebuilds a `TrackedRng` from a snapshot by reseeding and then replaying `words_drawn`
+    /// outputs, since neither `XorShiftRng` nor `ChaChaRng` expose a way to seek directly to an
+    /// arbitrary position in their stream.
+    fn resume(state: &RngState) -> TrackedRng {
+        let mut rng = TrackedRng::from_seed(state.seed);
+        for _ in 0..state.words_drawn {
+            let _ = rng.next_u32();
+        }
+        rng
+    }
+}
+
+impl Rng for TrackedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.words_drawn = self.words_drawn.wrapping_add(1);
+        self.backend.next_u32()
+    }
+}
 
 struct RandomBytesImpl {
     function_pointers: ffi::FunctionPointers,
     name: CString,
-    seed: [u32; 4],
+    seed: RngSeed,
+    seeding_mode: SeedingMode,
 }
 
 impl Default for RandomBytesImpl {
     fn default() -> RandomBytesImpl {
-        let seed = [rand::random(), rand::random(), rand::random(), rand::random()];
+        let seed = RngSeed::XorShift([rand::random(), rand::random(), rand::random(),
+                                       rand::random()]);
         RandomBytesImpl {
             function_pointers: ffi::FunctionPointers::default(),
             name: unwrap!(CString::new("Rust XorShiftRng")),
             seed: seed,
+            seeding_mode: SeedingMode::Shared,
         }
     }
 }
@@ -103,10 +291,10 @@ mod ffi {
             FunctionPointers {
                 implementation_name: implementation_name,
                 random: random,
-                stir: None,
-                uniform: None,
+                stir: Some(stir),
+                uniform: Some(uniform),
                 buf: buf,
-                close: None,
+                close: Some(close),
             }
         }
     }
@@ -125,6 +313,91 @@ mod ffi {
         super::RNG.with(|rng| rng.borrow_mut().gen())
     }
 
+    /// Draws an unbiased value in `[0, upper_bound)` from `random()` via rejection sampling,
+    /// avoiding the modulo bias libsodium's own default `randombytes_uniform` would otherwise
+    /// introduce.
+    extern "C" fn uniform(upper_bound: uint32_t) -> uint32_t {
+        if upper_bound < 2 {
+            return 0;
+        }
+        let min = (0u32.wrapping_sub(upper_bound)) % upper_bound;
+        loop {
+            let candidate = random();
+            if candidate >= min {
+                return candidate % upper_bound;
+            }
+        }
+    }
+
+    /// Reseeds the calling thread's PRNG so its output stream actually changes.  Deriving this
+    /// from a process-wide counter (rather than just rebuilding from the master seed, as
+    /// `tracked_rng_for_this_thread` would) ensures `stir()` advances the stream instead of
+    /// rewinding it back to its starting point and reproducing output already handed out.
+    extern "C" fn stir() {
+        let count = super::STIR_COUNTER.fetch_add(1, super::Ordering::Relaxed);
+        let seed = {
+            let random_bytes = unwrap!(super::RANDOM_BYTES_IMPL.lock());
+            super::derive_stir_seed(&random_bytes.seed, count)
+        };
+        super::RNG.with(|rng| *rng.borrow_mut() = super::TrackedRng::from_seed(seed));
+    }
+
+    /// Resets the initialisation state, allowing a subsequent `init_with_rng`/`init_with_chacha`
+    /// call to install a fresh implementation.
+    ///
+    /// This doesn't (and can't) undo libsodium's own process-wide initialisation, which is why
+    /// `init_with_seed` remembers that `sodium_init()` has already succeeded once and skips
+    /// calling it again on the re-init this enables -- otherwise libsodium would report `1`
+    /// ("already initialised"), which `init_with_seed` would wrongly treat as a fresh failure.
+    extern "C" fn close() -> c_int {
+        *unwrap!(super::INIT_RESULT.lock()) = None;
+        0
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use rand::{SeedableRng, XorShiftRng};
+        use super::super::{init_with_rng, SeedingMode};
+
+        #[test]
+        fn uniform_stays_in_range() {
+            let mut rng = XorShiftRng::from_seed([10, 20, 30, 40]);
+            unwrap!(init_with_rng(&mut rng, SeedingMode::Shared));
+
+            assert_eq!(0, uniform(0));
+            assert_eq!(0, uniform(1));
+            for _ in 0..1000 {
+                assert!(uniform(7) < 7);
+            }
+        }
+
+        #[test]
+        fn stir_changes_the_next_output() {
+            let mut rng = XorShiftRng::from_seed([50, 60, 70, 80]);
+            unwrap!(init_with_rng(&mut rng, SeedingMode::Shared));
+
+            let before: Vec<uint32_t> = (0..8).map(|_| random()).collect();
+            stir();
+            let after: Vec<uint32_t> = (0..8).map(|_| random()).collect();
+            assert!(before != after);
+        }
+
+        #[test]
+        fn close_then_reinit_succeeds() {
+            let mut rng = XorShiftRng::from_seed([90, 100, 110, 120]);
+            unwrap!(init_with_rng(&mut rng, SeedingMode::Shared));
+
+            assert_eq!(0, close());
+
+            // Before the fix in the commit this test accompanies, `sodium_init()` being called
+            // again here (since `close()` reset `INIT_RESULT` to `None`) would return `1`
+            // ("already initialised"), which was then wrongly stored as a failure, permanently
+            // bricking every future `init_*` call behind `Err(1)`.
+            assert_eq!(Ok(()), init_with_rng(&mut rng, SeedingMode::Shared));
+        }
+    }
+
     #[cfg_attr(feature="clippy", allow(cast_possible_wrap))]
     #[allow(unsafe_code)]
     extern "C" fn buf(buf: *mut c_void, size: size_t) {
@@ -152,16 +425,104 @@ mod ffi {
 /// apply this seeded RNG to libsodium has not been actioned.
 ///
 /// Each sodiumoxide function which uses the random generator in a new thread will cause a new
-/// thread-local instance of the PRNG to be created.  Each such instance will be seeded with the
-/// same value, meaning for example that two newly-spawned threads calling `box_::gen_keypair()`
-/// will generate identical keys.
+/// thread-local instance of the PRNG to be created.  With `mode` set to `SeedingMode::Shared`,
+/// each such instance will be seeded with the same value, meaning for example that two
+/// newly-spawned threads calling `box_::gen_keypair()` will generate identical keys.  Passing
+/// `SeedingMode::PerThread` instead derives a distinct-but-reproducible sub-seed for each thread,
+/// so spawned threads no longer collide.
+///
+/// The PRNG used is the (not cryptographically secure) `XorShiftRng`, seeded from `rng`.  This is
+/// fine for deterministic test vectors, but real key material should be generated via
+/// [`init_with_chacha`](fn.init_with_chacha.html) instead.
+#[allow(unsafe_code)]
+pub fn init_with_rng<T: Rng>(rng: &mut T, mode: SeedingMode) -> Result<(), i32> {
+    init_with_seed(RngSeed::XorShift([rng.gen(), rng.gen(), rng.gen(), rng.gen()]), mode)
+}
+
+/// Like [`init_with_rng`](fn.init_with_rng.html), but installs the built-in ChaCha20-based
+/// generator, seeded from `seed`, as the backend for libsodium's `randombytes`.
+///
+/// This is the recommended entry point for production use: unlike `XorShiftRng`, the ChaCha20
+/// generator is a cryptographically secure stream cipher, so output fed to functions such as
+/// `box_::gen_keypair()` is not predictable from a handful of observed outputs.
+#[allow(unsafe_code)]
+pub fn init_with_chacha(seed: [u8; 32]) -> Result<(), i32> {
+    init_with_seed(RngSeed::ChaCha(seed), SeedingMode::Shared)
+}
+
+/// Seeds the built-in ChaCha20 generator straight from the operating system's CSPRNG and installs
+/// it, exactly as [`init_with_chacha`](fn.init_with_chacha.html) would.
+///
+/// This is a "secure by default" one-call entry point: it doesn't depend on `rand`'s thread RNG
+/// having been seeded well, pulling the seed instead from `getrandom`/`getentropy`/
+/// `BCryptGenRandom` as appropriate for the host platform (falling back to a buffered read of
+/// `/dev/urandom` where the syscall isn't available).
+///
+/// Returns `Err(-2)` if the OS entropy source itself could not be read; otherwise the error
+/// semantics match [`init_with_rng`](fn.init_with_rng.html).
+pub fn init_from_os() -> Result<(), i32> {
+    init_from(os_rng::fill)
+}
+
+/// Like [`init_from_os`](fn.init_from_os.html), but always reads the seed via a buffered
+/// `/dev/urandom` file descriptor rather than the platform's CSPRNG syscall.
+///
+/// Intended for embedded or sandboxed callers who know ahead of time that the syscall path (e.g.
+/// `getrandom`/`getentropy`) will be unavailable or blocked, and would rather take the file-based
+/// fallback deliberately than pay for a failed syscall first.
+pub fn init_from_os_file_fallback() -> Result<(), i32> {
+    init_from(os_rng::fill_via_urandom_file)
+}
+
+/// With the `auto-init` feature enabled, installs the seeded ChaCha20 implementation (via
+/// [`init_from_os`](fn.init_from_os.html)) before `main` runs, so callers who never remember to
+/// call an `init_*` function still get a seeded CSPRNG instead of silently falling back to
+/// libsodium's own default.
+///
+/// This is opt-in behind the feature flag rather than unconditional: forcing a global-constructor
+/// init on every consumer of this crate would break callers who need a specific, reproducible
+/// seed and call an `init_*` function themselves.  A later explicit call to
+/// `init_with_rng`/`init_with_chacha` is still free to override whatever this installed, subject
+/// to the same multiple-call idempotency contract `INIT_RESULT` already provides.
+///
+/// Enabling this feature requires declaring `ctor` as an optional dependency behind it in this
+/// crate's Cargo.toml; see the `extern crate ctor` declaration near the top of this file.
+#[cfg(feature = "auto-init")]
+#[ctor::ctor]
+fn auto_init() {
+    // Best-effort: this runs before `main`, so there's no sensible way to surface a failure here
+    // beyond leaving libsodium to its own default RNG.
+    let _ = init_from_os();
+}
+
+fn init_from(fill: fn(&mut [u8]) -> io::Result<()>) -> Result<(), i32> {
+    let mut seed = [0u8; 32];
+    match fill(&mut seed) {
+        Ok(()) => init_with_chacha(seed),
+        Err(_) => Err(-2),
+    }
+}
+
 #[allow(unsafe_code)]
-pub fn init_with_rng<T: Rng>(rng: &mut T) -> Result<(), i32> {
-    let seed = [rng.gen(), rng.gen(), rng.gen(), rng.gen()];
+fn init_with_seed(seed: RngSeed, mode: SeedingMode) -> Result<(), i32> {
     let mut init_result = &mut *unwrap!(INIT_RESULT.lock());
     if let Some(ref existing_result) = *init_result {
         return if *existing_result == 0 {
-            Ok(RNG.with(|rng| *rng.borrow_mut() = XorShiftRng::from_seed(seed)))
+            // The libsodium installation itself only happens once, but the master seed and
+            // seeding mode governing how *future* (including not-yet-spawned) threads are seeded
+            // are updated on every call, so an explicit re-init fully overrides whatever was
+            // installed before it -- including a seed `auto_init` installed before `main` ran.
+            //
+            // `name` is deliberately left untouched here: libsodium's `implementation_name`
+            // callback hands out a raw pointer into it, and replacing the `CString` on every
+            // re-init would free the old buffer out from under any pointer still held from an
+            // earlier call, racing with a concurrent caller of that callback.
+            {
+                let random_bytes = &mut *unwrap!(RANDOM_BYTES_IMPL.lock());
+                random_bytes.seed = seed;
+                random_bytes.seeding_mode = mode;
+            }
+            Ok(RNG.with(|rng| *rng.borrow_mut() = TrackedRng::from_seed(seed)))
         } else {
             Err(*existing_result)
         };
@@ -169,17 +530,33 @@ pub fn init_with_rng<T: Rng>(rng: &mut T) -> Result<(), i32> {
     let mut sodium_result;
     {
         let random_bytes = &mut *unwrap!(RANDOM_BYTES_IMPL.lock());
+        random_bytes.name = name_for(&seed);
         random_bytes.seed = seed;
+        random_bytes.seeding_mode = mode;
         sodium_result =
             unsafe { ffi::randombytes_set_implementation(&mut random_bytes.function_pointers) };
     }
     match sodium_result {
-        0 => sodium_result = unsafe { ffi::sodium_init() },
+        0 => {
+            if SODIUM_EVER_INITIALIZED.load(Ordering::Acquire) {
+                // `ffi::close()` reset `INIT_RESULT` to `None`, sending us back through this
+                // "first call" branch, but libsodium itself was already initialised earlier in
+                // this process and has no way to un-initialise.  Calling `sodium_init()` again
+                // would just report `1` ("already initialised"), which is not a failure here --
+                // the `randombytes_set_implementation` call above already reapplied our (new)
+                // seed, which is all a post-close re-init needs to do.
+            } else {
+                sodium_result = unsafe { ffi::sodium_init() };
+                if sodium_result == 0 {
+                    SODIUM_EVER_INITIALIZED.store(true, Ordering::Release);
+                }
+            }
+        }
         _ => (),
     };
     // Since `ffi::sodium_init()` makes a call to `buf()`, reset the thread-local `RNG` so that it
     // yields consistent results with calls from new threads.
-    RNG.with(|rng| *rng.borrow_mut() = XorShiftRng::from_seed(seed));
+    RNG.with(|rng| *rng.borrow_mut() = tracked_rng_for_this_thread());
     *init_result = Some(sodium_result);
     match sodium_result {
         0 => Ok(()),
@@ -187,6 +564,129 @@ pub fn init_with_rng<T: Rng>(rng: &mut T) -> Result<(), i32> {
     }
 }
 
+fn name_for(seed: &RngSeed) -> CString {
+    match *seed {
+        RngSeed::XorShift(_) => unwrap!(CString::new("Rust XorShiftRng")),
+        RngSeed::ChaCha(_) => unwrap!(CString::new("Rust ChaCha20Rng")),
+    }
+}
+
+/// A snapshot of a thread-local generator's state -- its seed plus how many `u32` words have
+/// been drawn from it -- sufficient to resume its exact output stream later, possibly in another
+/// process.  Obtained via [`snapshot_state`](fn.snapshot_state.html) (or
+/// [`export_state`](fn.export_state.html) for the byte-blob form) and consumed by
+/// [`restore_state`](fn.restore_state.html) (or [`import_state`](fn.import_state.html)).
+///
+/// With the `serde` feature enabled, this also implements `Serialize`/`Deserialize`, so it can be
+/// embedded directly in a caller's own checkpoint format instead of going via the opaque byte
+/// blob `export_state`/`import_state` use.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RngState {
+    seed: RngSeed,
+    words_drawn: u64,
+}
+
+const STATE_TAG_XOR_SHIFT: u8 = 0;
+const STATE_TAG_CHA_CHA: u8 = 1;
+
+/// Snapshots the calling thread's generator state as an owned `RngState`, for callers who want to
+/// embed it directly in their own checkpoint format (typically serialized via the `serde`
+/// feature) instead of going via the opaque byte blob [`export_state`](fn.export_state.html)
+/// produces.
+pub fn snapshot_state() -> RngState {
+    RNG.with(|rng| rng.borrow().snapshot())
+}
+
+/// Restores the calling thread's generator from an `RngState` produced by
+/// [`snapshot_state`](fn.snapshot_state.html), resuming the exact same output stream.
+pub fn restore_state(state: RngState) {
+    RNG.with(|rng| *rng.borrow_mut() = TrackedRng::resume(&state));
+}
+
+/// Snapshots the calling thread's generator state into a compact byte blob that
+/// [`import_state`](fn.import_state.html) can later restore, in this process or another.
+pub fn export_state() -> Vec<u8> {
+    let state = snapshot_state();
+    let mut bytes = Vec::new();
+    match state.seed {
+        RngSeed::XorShift(words) => {
+            bytes.push(STATE_TAG_XOR_SHIFT);
+            for word in &words {
+                push_u32_le(&mut bytes, *word);
+            }
+        }
+        RngSeed::ChaCha(seed_bytes) => {
+            bytes.push(STATE_TAG_CHA_CHA);
+            bytes.extend_from_slice(&seed_bytes);
+        }
+    }
+    push_u64_le(&mut bytes, state.words_drawn);
+    bytes
+}
+
+/// Restores the calling thread's generator from a blob produced by
+/// [`export_state`](fn.export_state.html), resuming the exact same output stream.
+///
+/// Returns `Err(-3)` if `bytes` doesn't start with a XorShift or ChaCha20 tag, or is shorter than
+/// the blob `export_state` writes for that tag.  The seed and word count are not otherwise
+/// checked, and bytes past the end of the blob are ignored.
+pub fn import_state(bytes: &[u8]) -> Result<(), i32> {
+    let state = match decode_state(bytes) {
+        Some(state) => state,
+        None => return Err(-3),
+    };
+    restore_state(state);
+    Ok(())
+}
+
+fn decode_state(bytes: &[u8]) -> Option<RngState> {
+    let (seed, rest) = match bytes.split_first() {
+        Some((&STATE_TAG_XOR_SHIFT, rest)) if rest.len() >= 16 + 8 => {
+            let mut words = [0u32; 4];
+            for (i, word) in words.iter_mut().enumerate() {
+                *word = read_u32_le(&rest[i * 4..i * 4 + 4]);
+            }
+            (RngSeed::XorShift(words), &rest[16..])
+        }
+        Some((&STATE_TAG_CHA_CHA, rest)) if rest.len() >= 32 + 8 => {
+            let mut seed_bytes = [0u8; 32];
+            seed_bytes.copy_from_slice(&rest[..32]);
+            (RngSeed::ChaCha(seed_bytes), &rest[32..])
+        }
+        _ => return None,
+    };
+    Some(RngState {
+        seed: seed,
+        words_drawn: read_u64_le(&rest[..8]),
+    })
+}
+
+fn push_u32_le(out: &mut Vec<u8>, value: u32) {
+    out.push(value as u8);
+    out.push((value >> 8) as u8);
+    out.push((value >> 16) as u8);
+    out.push((value >> 24) as u8);
+}
+
+fn push_u64_le(out: &mut Vec<u8>, value: u64) {
+    for i in 0..8 {
+        out.push((value >> (8 * i)) as u8);
+    }
+}
+
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) | (u32::from(bytes[1]) << 8) | (u32::from(bytes[2]) << 16) |
+    (u32::from(bytes[3]) << 24)
+}
+
+fn read_u64_le(bytes: &[u8]) -> u64 {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate() {
+        value |= u64::from(*byte) << (8 * i);
+    }
+    value
+}
+
 
 
 #[cfg(test)]
@@ -199,10 +699,10 @@ mod tests {
     #[test]
     fn seeded() {
         let mut rng = XorShiftRng::from_seed([0, 1, 2, 3]);
-        unwrap!(init_with_rng(&mut rng));
+        unwrap!(init_with_rng(&mut rng, SeedingMode::Shared));
 
         // Initialise again - should succeed.
-        unwrap!(init_with_rng(&mut rng));
+        unwrap!(init_with_rng(&mut rng, SeedingMode::Shared));
 
         let expected_public_key = [116, 196, 172, 118, 77, 124, 253, 254, 156, 51, 141, 193, 20,
                                    160, 227, 232, 231, 20, 24, 151, 207, 45, 202, 250, 85, 96,
@@ -226,5 +726,57 @@ mod tests {
         }));
         unwrap!(child1.join());
         unwrap!(child2.join());
+
+        // Switching to `PerThread` mode means newly-spawned threads no longer collide with each
+        // other.
+        unwrap!(init_with_rng(&mut rng, SeedingMode::PerThread));
+
+        let child3 = unwrap!(Builder::new()
+            .name("child3".to_string())
+            .spawn(move || box_::gen_keypair()));
+        let child4 = unwrap!(Builder::new()
+            .name("child4".to_string())
+            .spawn(move || box_::gen_keypair()));
+        let (public_key3, _) = unwrap!(child3.join());
+        let (public_key4, _) = unwrap!(child4.join());
+        assert!(public_key3.0 != public_key4.0);
+    }
+
+    #[test]
+    fn export_then_import_state_resumes_the_same_stream() {
+        let mut rng = XorShiftRng::from_seed([4, 5, 6, 7]);
+        unwrap!(init_with_rng(&mut rng, SeedingMode::Shared));
+
+        // Draw some output so `words_drawn` is non-zero, then snapshot at this point.
+        let _ = box_::gen_keypair();
+        let blob = export_state();
+
+        // Advancing the live generator from here is what `import_state` below should reproduce.
+        let (expected_public_key, expected_private_key) = box_::gen_keypair();
+
+        unwrap!(import_state(&blob));
+        let (public_key, private_key) = box_::gen_keypair();
+        assert_eq!(expected_public_key.0, public_key.0);
+        assert_eq!(expected_private_key.0, private_key.0);
+
+        assert_eq!(Err(-3), import_state(&[0xff; 3]));
+    }
+
+    #[test]
+    fn snapshot_then_restore_state_resumes_the_same_stream() {
+        let mut rng = XorShiftRng::from_seed([8, 9, 10, 11]);
+        unwrap!(init_with_rng(&mut rng, SeedingMode::Shared));
+
+        // Draw some output so `words_drawn` is non-zero, then snapshot at this point.
+        let _ = box_::gen_keypair();
+        let state = snapshot_state();
+
+        // Advancing the live generator from here is what `restore_state` below should reproduce.
+        let (expected_public_key, expected_private_key) = box_::gen_keypair();
+
+        restore_state(state);
+        let (public_key, private_key) = box_::gen_keypair();
+        assert_eq!(expected_public_key.0, public_key.0);
+        assert_eq!(expected_private_key.0, private_key.0);
     }
 }
@@ -0,0 +1,142 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! OS-entropy seeding, in the spirit of the `getrandom` crate: prefer the platform's CSPRNG
+//! syscall and fall back to a buffered read from `/dev/urandom` when that syscall isn't available
+//! (e.g. inside some sandboxes or on older kernels).
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+/// Fills `buf` with cryptographically secure entropy from the operating system, using the
+/// fastest path the current platform supports.
+pub fn fill(buf: &mut [u8]) -> io::Result<()> {
+    imp::fill(buf)
+}
+
+/// Fills `buf` by reading from `/dev/urandom` through a buffered file descriptor.
+///
+/// This is the fallback path used when the platform's CSPRNG syscall isn't available, and can
+/// also be selected explicitly by embedded/sandboxed callers that know the syscall will be
+/// blocked (e.g. by a restrictive `seccomp` filter).
+pub fn fill_via_urandom_file(buf: &mut [u8]) -> io::Result<()> {
+    let mut file = BufReader::new(File::open("/dev/urandom")?);
+    file.read_exact(buf)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use libc::{c_void, size_t};
+    use std::io;
+    use super::fill_via_urandom_file;
+
+    /// Pulls entropy from the `getrandom(2)` syscall, falling back to `/dev/urandom` if the
+    /// kernel doesn't support it (pre-3.17) or the call is otherwise blocked.
+    ///
+    /// Uses `libc::SYS_getrandom` rather than a hand-rolled syscall number: the number differs
+    /// per architecture (e.g. 318 on x86_64, 278 on aarch64, 355 on i686), and `libc` already
+    /// gets this right for every target it supports.
+    pub fn fill(buf: &mut [u8]) -> io::Result<()> {
+        #[allow(unsafe_code)]
+        let written = unsafe {
+            libc::syscall(libc::SYS_getrandom,
+                          buf.as_mut_ptr() as *mut c_void,
+                          buf.len() as size_t,
+                          0)
+        };
+        if written >= 0 && written as usize == buf.len() {
+            Ok(())
+        } else {
+            fill_via_urandom_file(buf)
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod imp {
+    use libc::{c_int, c_void, size_t};
+    use std::io;
+    use super::fill_via_urandom_file;
+
+    extern "C" {
+        fn getentropy(buf: *mut c_void, buflen: size_t) -> c_int;
+    }
+
+    /// Pulls entropy from `getentropy(2)`, falling back to `/dev/urandom` if it isn't available
+    /// (`getentropy` caps a single call at 256 bytes, so a 32-byte seed always fits in one call).
+    pub fn fill(buf: &mut [u8]) -> io::Result<()> {
+        #[allow(unsafe_code)]
+        let result = unsafe { getentropy(buf.as_mut_ptr() as *mut c_void, buf.len() as size_t) };
+        if result == 0 {
+            Ok(())
+        } else {
+            fill_via_urandom_file(buf)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use libc::{c_int, c_ulong, c_void};
+    use std::io;
+
+    const BCRYPT_USE_SYSTEM_PREFERRED_RNG: c_ulong = 0x0000_0002;
+
+    #[link(name = "bcrypt")]
+    extern "system" {
+        fn BCryptGenRandom(algorithm: *mut c_void,
+                            buffer: *mut u8,
+                            buffer_len: c_ulong,
+                            flags: c_ulong)
+                            -> c_int;
+    }
+
+    /// Pulls entropy straight from `BCryptGenRandom`'s system-preferred CSPRNG.
+    pub fn fill(buf: &mut [u8]) -> io::Result<()> {
+        #[allow(unsafe_code)]
+        let status = unsafe {
+            BCryptGenRandom(::std::ptr::null_mut(),
+                             buf.as_mut_ptr(),
+                             buf.len() as c_ulong,
+                             BCRYPT_USE_SYSTEM_PREFERRED_RNG)
+        };
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::Other, "BCryptGenRandom failed"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_does_not_leave_the_buffer_untouched() {
+        let mut buf = [0u8; 32];
+        unwrap!(fill(&mut buf));
+        assert!(buf.iter().any(|byte| *byte != 0));
+    }
+
+    #[test]
+    fn fill_via_urandom_file_does_not_leave_the_buffer_untouched() {
+        let mut buf = [0u8; 32];
+        unwrap!(fill_via_urandom_file(&mut buf));
+        assert!(buf.iter().any(|byte| *byte != 0));
+    }
+}